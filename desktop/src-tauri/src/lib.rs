@@ -1,42 +1,706 @@
-use reqwest::header::{HeaderMap, HeaderValue, ORIGIN, REFERER, CONTENT_TYPE};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use base64::Engine;
+use futures_util::StreamExt;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE,
+    CONTENT_TYPE, ORIGIN, RANGE, REFERER, USER_AGENT,
+};
+use reqwest::redirect::Policy;
+use reqwest::Method;
 use serde::{Deserialize, Serialize};
+use tauri::http::{Request as HttpRequest, Response as HttpResponseMessage, StatusCode};
+use tauri::{AppHandle, Emitter, Manager, State, UriSchemeContext, UriSchemeResponder, Wry};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+use tokio_util::sync::CancellationToken;
+
+/// One part of a `multipart` request body: either an inline field or a file
+/// read from disk and streamed rather than buffered.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum MultipartPart {
+    Field {
+        name: String,
+        value: String,
+    },
+    File {
+        name: String,
+        file_path: PathBuf,
+        #[serde(default)]
+        mime: Option<String>,
+        #[serde(default)]
+        filename: Option<String>,
+    },
+}
+
+/// Body payload for an outgoing `api_fetch` request.
+///
+/// Tagged as `{ "type": "json" | "text" | "form" | "multipart", "value": ... }`
+/// on the JS side.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+enum RequestBody {
+    Json(serde_json::Value),
+    Text(String),
+    Form(HashMap<String, String>),
+    Multipart(Vec<MultipartPart>),
+}
+
+/// Builds a `reqwest` multipart form from the parsed part list, streaming
+/// file parts from disk instead of buffering them in memory.
+async fn build_multipart_form(parts: Vec<MultipartPart>) -> Result<reqwest::multipart::Form, String> {
+    let mut form = reqwest::multipart::Form::new();
+    for part in parts {
+        form = match part {
+            MultipartPart::Field { name, value } => form.text(name, value),
+            MultipartPart::File {
+                name,
+                file_path,
+                mime,
+                filename,
+            } => {
+                let file = tokio::fs::File::open(&file_path)
+                    .await
+                    .map_err(|e| format!("Failed to open `{}`: {}", file_path.display(), e))?;
+                let file_size = file
+                    .metadata()
+                    .await
+                    .map_err(|e| format!("Failed to stat `{}`: {}", file_path.display(), e))?
+                    .len();
+                let filename = filename.unwrap_or_else(|| {
+                    file_path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default()
+                });
+
+                let mut file_part = reqwest::multipart::Part::stream_with_length(
+                    reqwest::Body::wrap_stream(ReaderStream::new(file)),
+                    file_size,
+                )
+                .file_name(filename);
+                if let Some(mime) = mime {
+                    file_part = file_part
+                        .mime_str(&mime)
+                        .map_err(|e| format!("Invalid mime type `{}`: {}", mime, e))?;
+                }
+                form.part(name, file_part)
+            }
+        };
+    }
+    Ok(form)
+}
+
+/// How the response body should be decoded and handed back to the frontend.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ResponseType {
+    #[default]
+    Json,
+    Text,
+    Binary,
+}
+
+/// Options accepted by `api_fetch`, replacing the old bare `(url, method)` pair.
+#[derive(Debug, Deserialize)]
+struct FetchOptions {
+    url: String,
+    #[serde(default)]
+    method: Option<String>,
+    /// Overrides/additions to the default header set, keyed by header name.
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    query: HashMap<String, String>,
+    #[serde(default)]
+    body: Option<RequestBody>,
+    #[serde(default = "default_follow_redirects")]
+    follow_redirects: bool,
+    #[serde(default)]
+    max_redirections: Option<usize>,
+    #[serde(default)]
+    connect_timeout: Option<u64>,
+    /// Reqwest has no distinct read-timeout knob; this falls back to the
+    /// overall request `timeout` when that isn't set explicitly.
+    #[serde(default)]
+    read_timeout: Option<u64>,
+    #[serde(default)]
+    timeout: Option<u64>,
+    #[serde(default)]
+    response_type: ResponseType,
+    /// Overrides the default `User-Agent: OpenNotesAPI/<version>` header.
+    #[serde(default)]
+    user_agent: Option<String>,
+    /// Send the project's `Origin` header. Most third-party APIs don't expect
+    /// this and some reject spoofed origins, so it's off unless requested.
+    #[serde(default)]
+    include_origin: bool,
+    /// Send the project's `Referer` header. Off by default for the same
+    /// reason as `include_origin`.
+    #[serde(default)]
+    include_referer: bool,
+}
+
+fn default_follow_redirects() -> bool {
+    true
+}
 
-#[derive(Debug, Serialize, Deserialize)]
+fn default_user_agent() -> String {
+    format!("OpenNotesAPI/{}", env!("CARGO_PKG_VERSION"))
+}
+
+#[derive(Debug, Serialize)]
 pub struct HttpResponse {
     pub status: u16,
-    pub body: String,
     pub ok: bool,
+    /// Response header values, keyed by header name. A `Vec` because headers
+    /// like `Set-Cookie` legitimately repeat across a single response.
+    pub headers: HashMap<String, Vec<String>>,
+    pub body: String,
+    /// True when `body` is base64-encoded binary data rather than text.
+    pub is_base64: bool,
+}
+
+/// Pooled, managed `reqwest::Client`. Built once in `run()` so requests reuse
+/// connections and TLS sessions instead of paying handshake cost every call.
+struct HttpClient(reqwest::Client);
+
+/// Caches the one-off clients built for requests that customize redirect
+/// policy or connect timeout (both are client-build-time settings in
+/// reqwest, so they can't be applied to the shared pooled `HttpClient`).
+/// Keyed by the exact combination requested, so repeat calls with the same
+/// non-default settings reuse a connection pool instead of paying a fresh
+/// handshake every time.
+#[derive(Default)]
+struct DedicatedClientCache(Mutex<HashMap<(bool, Option<usize>, Option<u64>), reqwest::Client>>);
+
+fn build_http_client() -> reqwest::Client {
+    let mut default_headers = HeaderMap::new();
+    default_headers.insert(
+        USER_AGENT,
+        HeaderValue::from_str(&default_user_agent()).expect("default User-Agent is valid"),
+    );
+
+    reqwest::Client::builder()
+        .pool_idle_timeout(Duration::from_secs(90))
+        .gzip(true)
+        .brotli(true)
+        .default_headers(default_headers)
+        .build()
+        .expect("failed to build default HTTP client")
 }
 
 #[tauri::command]
-async fn api_fetch(url: String, method: Option<String>) -> Result<HttpResponse, String> {
-    let client = reqwest::Client::new();
-    
+async fn api_fetch(
+    client: State<'_, HttpClient>,
+    dedicated_clients: State<'_, DedicatedClientCache>,
+    options: FetchOptions,
+) -> Result<HttpResponse, String> {
+    let method = options
+        .method
+        .as_deref()
+        .unwrap_or("GET")
+        .to_uppercase()
+        .parse::<Method>()
+        .map_err(|e| format!("Invalid method: {}", e))?;
+
+    // Form and Multipart bodies set their own Content-Type (with a boundary,
+    // for multipart) via their respective reqwest builder methods, so the
+    // default below must be skipped for them or the request goes out with
+    // two Content-Type headers.
+    let body_sets_own_content_type =
+        matches!(options.body, Some(RequestBody::Form(_)) | Some(RequestBody::Multipart(_)));
+
     let mut headers = HeaderMap::new();
-    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-    headers.insert(ORIGIN, HeaderValue::from_static("https://nagusamecs.github.io"));
-    headers.insert(REFERER, HeaderValue::from_static("https://nagusamecs.github.io/OpenNotesAPI/"));
-    
-    let method_str = method.unwrap_or_else(|| "GET".to_string());
-    
-    let request_builder = match method_str.to_uppercase().as_str() {
-        "POST" => client.post(&url),
-        "PUT" => client.put(&url),
-        "DELETE" => client.delete(&url),
-        _ => client.get(&url),
-    };
-    
+    if !body_sets_own_content_type {
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
+
+    let user_agent = options.user_agent.clone().unwrap_or_else(default_user_agent);
+    headers.insert(
+        USER_AGENT,
+        HeaderValue::from_str(&user_agent).map_err(|e| format!("Invalid User-Agent: {}", e))?,
+    );
+
+    if options.include_origin {
+        headers.insert(ORIGIN, HeaderValue::from_static("https://nagusamecs.github.io"));
+    }
+    if options.include_referer {
+        headers.insert(
+            REFERER,
+            HeaderValue::from_static("https://nagusamecs.github.io/OpenNotesAPI/"),
+        );
+    }
+
+    for (name, value) in &options.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| format!("Invalid header name `{}`: {}", name, e))?;
+        // reqwest's .form()/.multipart() both set their own Content-Type (the
+        // latter with a boundary) via `.header()`, which appends rather than
+        // replaces — so a caller override here would ride along as a second,
+        // conflicting Content-Type rather than actually overriding anything.
+        if body_sets_own_content_type && name == CONTENT_TYPE {
+            continue;
+        }
+        let value = HeaderValue::from_str(value)
+            .map_err(|e| format!("Invalid header value for `{}`: {}", name, e))?;
+        headers.insert(name, value);
+    }
+
+    // The redirect policy and connect timeout are set at client-build time in
+    // reqwest, so a request that customizes either can't reuse the pooled
+    // client. It gets a dedicated client instead, cached by the exact
+    // settings requested so repeat calls reuse its connection pool too.
+    let needs_dedicated_client = !options.follow_redirects
+        || options.max_redirections.is_some()
+        || options.connect_timeout.is_some();
+
+    let dedicated_client = if needs_dedicated_client {
+        let key = (
+            options.follow_redirects,
+            options.max_redirections,
+            options.connect_timeout,
+        );
+
+        let cached = dedicated_clients.0.lock().unwrap().get(&key).cloned();
+        let client = match cached {
+            Some(client) => client,
+            None => {
+                let redirect_policy = if !options.follow_redirects {
+                    Policy::none()
+                } else if let Some(max) = options.max_redirections {
+                    Policy::limited(max)
+                } else {
+                    Policy::default()
+                };
+
+                let mut client_builder = reqwest::Client::builder().redirect(redirect_policy);
+                if let Some(secs) = options.connect_timeout {
+                    client_builder = client_builder.connect_timeout(Duration::from_secs(secs));
+                }
+                let client = client_builder
+                    .build()
+                    .map_err(|e| format!("Failed to build client: {}", e))?;
+                dedicated_clients.0.lock().unwrap().insert(key, client.clone());
+                client
+            }
+        };
+        Some(client)
+    } else {
+        None
+    };
+
+    let mut request_builder = dedicated_client
+        .as_ref()
+        .unwrap_or(&client.0)
+        .request(method, &options.url)
+        .headers(headers);
+
+    if let Some(secs) = options.timeout.or(options.read_timeout) {
+        request_builder = request_builder.timeout(Duration::from_secs(secs));
+    }
+
+    if !options.query.is_empty() {
+        request_builder = request_builder.query(&options.query);
+    }
+
+    request_builder = match options.body {
+        Some(RequestBody::Json(value)) => request_builder.json(&value),
+        Some(RequestBody::Text(text)) => request_builder.body(text),
+        Some(RequestBody::Form(fields)) => request_builder.form(&fields),
+        Some(RequestBody::Multipart(parts)) => {
+            let form = build_multipart_form(parts).await?;
+            request_builder.multipart(form)
+        }
+        None => request_builder,
+    };
+
     let response = request_builder
-        .headers(headers)
         .send()
         .await
         .map_err(|e| format!("Request failed: {}", e))?;
-    
+
     let status = response.status().as_u16();
     let ok = response.status().is_success();
-    let body = response.text().await.map_err(|e| format!("Failed to read body: {}", e))?;
-    
-    Ok(HttpResponse { status, body, ok })
+    let mut headers: HashMap<String, Vec<String>> = HashMap::new();
+    for (name, value) in response.headers() {
+        if let Ok(value) = value.to_str() {
+            headers.entry(name.to_string()).or_default().push(value.to_string());
+        }
+    }
+
+    let (body, is_base64) = match options.response_type {
+        ResponseType::Json => {
+            let bytes = response
+                .bytes()
+                .await
+                .map_err(|e| format!("Failed to read body: {}", e))?;
+            // An empty body (e.g. 204 No Content) is a success, not malformed
+            // JSON — treat it as `null` instead of erroring.
+            let body = if bytes.is_empty() {
+                "null".to_string()
+            } else {
+                let value: serde_json::Value = serde_json::from_slice(&bytes)
+                    .map_err(|e| format!("Failed to parse JSON body: {}", e))?;
+                serde_json::to_string(&value)
+                    .map_err(|e| format!("Failed to serialize JSON body: {}", e))?
+            };
+            (body, false)
+        }
+        ResponseType::Text => {
+            let body = response
+                .text()
+                .await
+                .map_err(|e| format!("Failed to read body: {}", e))?;
+            (body, false)
+        }
+        ResponseType::Binary => {
+            let bytes = response
+                .bytes()
+                .await
+                .map_err(|e| format!("Failed to read body: {}", e))?;
+            (base64::engine::general_purpose::STANDARD.encode(bytes), true)
+        }
+    };
+
+    Ok(HttpResponse {
+        status,
+        ok,
+        headers,
+        body,
+        is_base64,
+    })
+}
+
+/// Tracks in-flight `api_fetch_stream` downloads so they can be cancelled
+/// mid-transfer by `request_id`.
+#[derive(Default)]
+struct DownloadCancellationTokens(Mutex<HashMap<String, CancellationToken>>);
+
+#[derive(Clone, Serialize)]
+struct DownloadProgressPayload {
+    request_id: String,
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+#[derive(Clone, Serialize)]
+struct DownloadFinishedPayload {
+    request_id: String,
+    path: String,
+}
+
+/// Downloads `url` to `destination` on disk, emitting `http://download-progress`
+/// events as chunks arrive and a final `http://download-finished` event on
+/// completion. Cancel in-flight via `cancel_fetch_stream(request_id)`.
+#[tauri::command]
+async fn api_fetch_stream(
+    app: AppHandle,
+    client: State<'_, HttpClient>,
+    tokens: State<'_, DownloadCancellationTokens>,
+    request_id: String,
+    url: String,
+    destination: PathBuf,
+    headers: Option<HashMap<String, String>>,
+) -> Result<(), String> {
+    let token = CancellationToken::new();
+    tokens
+        .0
+        .lock()
+        .unwrap()
+        .insert(request_id.clone(), token.clone());
+
+    let result = run_fetch_stream(
+        &app,
+        &client.0,
+        &request_id,
+        &url,
+        &destination,
+        headers.unwrap_or_default(),
+        token,
+    )
+    .await;
+
+    tokens.0.lock().unwrap().remove(&request_id);
+    result
+}
+
+async fn run_fetch_stream(
+    app: &AppHandle,
+    client: &reqwest::Client,
+    request_id: &str,
+    url: &str,
+    destination: &Path,
+    headers: HashMap<String, String>,
+    token: CancellationToken,
+) -> Result<(), String> {
+    let mut request_builder = client.get(url);
+    for (name, value) in &headers {
+        request_builder = request_builder.header(name, value);
+    }
+
+    let response = request_builder
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+    let total = response.content_length();
+
+    let mut file = tokio::fs::File::create(destination)
+        .await
+        .map_err(|e| format!("Failed to create destination file: {}", e))?;
+
+    let mut downloaded: u64 = 0;
+    let mut stream = response.bytes_stream();
+
+    loop {
+        tokio::select! {
+            _ = token.cancelled() => {
+                return Err("Download cancelled".to_string());
+            }
+            chunk = stream.next() => {
+                match chunk {
+                    Some(Ok(bytes)) => {
+                        file.write_all(&bytes)
+                            .await
+                            .map_err(|e| format!("Failed to write chunk: {}", e))?;
+                        downloaded += bytes.len() as u64;
+                        let _ = app.emit(
+                            "http://download-progress",
+                            DownloadProgressPayload {
+                                request_id: request_id.to_string(),
+                                downloaded,
+                                total,
+                            },
+                        );
+                    }
+                    Some(Err(e)) => return Err(format!("Stream error: {}", e)),
+                    None => break,
+                }
+            }
+        }
+    }
+
+    file.flush()
+        .await
+        .map_err(|e| format!("Failed to flush file: {}", e))?;
+
+    let _ = app.emit(
+        "http://download-finished",
+        DownloadFinishedPayload {
+            request_id: request_id.to_string(),
+            path: destination.to_string_lossy().to_string(),
+        },
+    );
+
+    Ok(())
+}
+
+/// Aborts an in-flight `api_fetch_stream` download started with the same `request_id`.
+#[tauri::command]
+fn cancel_fetch_stream(tokens: State<'_, DownloadCancellationTokens>, request_id: String) {
+    if let Some(token) = tokens.0.lock().unwrap().remove(&request_id) {
+        token.cancel();
+    }
+}
+
+/// Serves cached note bodies and attachments straight from disk as
+/// `opennotes://localhost/<relative-path>` (only the URI path is used, resolved
+/// against the app cache's `notes-cache` directory) so the webview can load them (including via
+/// `<img>`/`<audio>` `src`) without round-tripping through `api_fetch` and
+/// base64. Supports byte-range requests for media scrubbing/previews.
+fn opennotes_protocol_handler(
+    ctx: UriSchemeContext<'_, Wry>,
+    request: HttpRequest<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let app = ctx.app_handle().clone();
+    tauri::async_runtime::spawn(async move {
+        responder.respond(resolve_opennotes_request(&app, &request).await);
+    });
+}
+
+async fn resolve_opennotes_request(
+    app: &AppHandle,
+    request: &HttpRequest<Vec<u8>>,
+) -> HttpResponseMessage<Vec<u8>> {
+    let relative_path = percent_encoding::percent_decode_str(request.uri().path().trim_start_matches('/'))
+        .decode_utf8_lossy()
+        .into_owned();
+
+    let cache_dir = match app.path().app_cache_dir() {
+        Ok(dir) => dir.join("notes-cache"),
+        Err(e) => return opennotes_error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to resolve cache dir: {}", e)),
+    };
+
+    // Canonicalize both the cache root and the requested path and verify the
+    // latter still lives under the former, so a `..`-laden or symlinked
+    // request path can't escape the cache directory onto arbitrary files.
+    let canonical_cache_dir = match tokio::fs::canonicalize(&cache_dir).await {
+        Ok(dir) => dir,
+        Err(_) => return opennotes_error(StatusCode::NOT_FOUND, format!("Not found: {}", relative_path)),
+    };
+    let canonical_path = match tokio::fs::canonicalize(cache_dir.join(&relative_path)).await {
+        Ok(path) => path,
+        Err(_) => return opennotes_error(StatusCode::NOT_FOUND, format!("Not found: {}", relative_path)),
+    };
+    if !canonical_path.starts_with(&canonical_cache_dir) {
+        return opennotes_error(StatusCode::NOT_FOUND, format!("Not found: {}", relative_path));
+    }
+
+    let mut file = match tokio::fs::File::open(&canonical_path).await {
+        Ok(file) => file,
+        Err(_) => return opennotes_error(StatusCode::NOT_FOUND, format!("Not found: {}", relative_path)),
+    };
+
+    let file_size = match file.metadata().await {
+        Ok(metadata) => metadata.len(),
+        Err(e) => return opennotes_error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to stat file: {}", e)),
+    };
+
+    let mime = mime_guess::from_path(&relative_path).first_or_octet_stream();
+    let range = request
+        .headers()
+        .get(RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_range_header(value, file_size));
+
+    let Some((start, end)) = range else {
+        let mut body = Vec::with_capacity(file_size as usize);
+        if let Err(e) = file.read_to_end(&mut body).await {
+            return opennotes_error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read file: {}", e));
+        }
+        return HttpResponseMessage::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, mime.as_ref())
+            .header(CONTENT_LENGTH, file_size.to_string())
+            .header(ACCEPT_RANGES, "bytes")
+            .body(body)
+            .unwrap_or_else(|_| opennotes_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to build response".into()));
+    };
+
+    let length = end - start + 1;
+    if file.seek(std::io::SeekFrom::Start(start)).await.is_err() {
+        return opennotes_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to seek file".into());
+    }
+    let mut body = vec![0u8; length as usize];
+    if let Err(e) = file.read_exact(&mut body).await {
+        return opennotes_error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read range: {}", e));
+    }
+
+    HttpResponseMessage::builder()
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(CONTENT_TYPE, mime.as_ref())
+        .header(CONTENT_LENGTH, length.to_string())
+        .header(CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, file_size))
+        .header(ACCEPT_RANGES, "bytes")
+        .body(body)
+        .unwrap_or_else(|_| opennotes_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to build response".into()))
+}
+
+/// Parses a single-range `Range: bytes=start-end` header, including the
+/// suffix form `bytes=-N` (the last `N` bytes of the file) per RFC 7233 §2.1.
+/// Multi-range requests aren't supported; callers fall back to a full `200`
+/// response.
+fn parse_range_header(value: &str, file_size: u64) -> Option<(u64, u64)> {
+    if file_size == 0 {
+        return None;
+    }
+
+    let spec = value.strip_prefix("bytes=")?;
+    let (start, end) = spec.split_once('-')?;
+
+    let (start, end) = if start.is_empty() {
+        let suffix_length: u64 = end.parse().ok()?;
+        if suffix_length == 0 {
+            return None;
+        }
+        (file_size.saturating_sub(suffix_length), file_size - 1)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = if end.is_empty() {
+            file_size - 1
+        } else {
+            end.parse().ok()?
+        };
+        (start, end)
+    };
+
+    if start >= file_size || start > end {
+        return None;
+    }
+    Some((start, end.min(file_size - 1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_open_ended_range() {
+        assert_eq!(parse_range_header("bytes=10-", 100), Some((10, 99)));
+    }
+
+    #[test]
+    fn parses_bounded_range() {
+        assert_eq!(parse_range_header("bytes=10-20", 100), Some((10, 20)));
+    }
+
+    #[test]
+    fn parses_suffix_range() {
+        assert_eq!(parse_range_header("bytes=-500", 1000), Some((500, 999)));
+    }
+
+    #[test]
+    fn suffix_range_longer_than_file_clamps_to_start() {
+        assert_eq!(parse_range_header("bytes=-5000", 1000), Some((0, 999)));
+    }
+
+    #[test]
+    fn bounded_range_end_past_file_size_is_clamped() {
+        assert_eq!(parse_range_header("bytes=10-5000", 1000), Some((10, 999)));
+    }
+
+    #[test]
+    fn rejects_missing_bytes_prefix() {
+        assert_eq!(parse_range_header("10-20", 100), None);
+    }
+
+    #[test]
+    fn rejects_malformed_spec() {
+        assert_eq!(parse_range_header("bytes=abc-def", 100), None);
+    }
+
+    #[test]
+    fn rejects_start_past_file_size() {
+        assert_eq!(parse_range_header("bytes=100-200", 100), None);
+    }
+
+    #[test]
+    fn rejects_start_after_end() {
+        assert_eq!(parse_range_header("bytes=50-10", 100), None);
+    }
+
+    #[test]
+    fn rejects_zero_length_suffix() {
+        assert_eq!(parse_range_header("bytes=-0", 100), None);
+    }
+
+    #[test]
+    fn rejects_empty_file() {
+        assert_eq!(parse_range_header("bytes=0-10", 0), None);
+    }
+}
+
+fn opennotes_error(status: StatusCode, message: String) -> HttpResponseMessage<Vec<u8>> {
+    HttpResponseMessage::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/plain")
+        .body(message.into_bytes())
+        .unwrap_or_else(|_| HttpResponseMessage::new(Vec::new()))
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -46,7 +710,15 @@ pub fn run() {
     .plugin(tauri_plugin_store::Builder::new().build())
     .plugin(tauri_plugin_shell::init())
     .plugin(tauri_plugin_http::init())
-    .invoke_handler(tauri::generate_handler![api_fetch])
+    .register_asynchronous_uri_scheme_protocol("opennotes", opennotes_protocol_handler)
+    .manage(HttpClient(build_http_client()))
+    .manage(DedicatedClientCache::default())
+    .manage(DownloadCancellationTokens::default())
+    .invoke_handler(tauri::generate_handler![
+      api_fetch,
+      api_fetch_stream,
+      cancel_fetch_stream
+    ])
     .setup(|app| {
       if cfg!(debug_assertions) {
         app.handle().plugin(